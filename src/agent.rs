@@ -0,0 +1,202 @@
+use crate::ai::{self, AIDifficulty};
+use crate::errors::Error;
+use crate::types::{Action, GameState, LegalMoves, Score};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// An external engine that picks a move given the current position, in the
+/// spirit of a wasm module being handed the board each turn: unlike
+/// `Strategy`, `choose` is not trusted with an RNG of ours, so an agent that
+/// wants randomness (see `RandomAgent`) has to own its own source of one.
+pub trait Agent {
+    fn choose(&mut self, state: &GameState, legal: &LegalMoves) -> Action;
+}
+
+/// Play one game between `agent_x` (player1) and `agent_o` (player2),
+/// starting from `start`, alternating turns and validating every move an
+/// agent returns against `legal_moves()` before applying it.  Returns the
+/// moves actually played together with the terminal `Score`, or the first
+/// `Error::IllegalMove` an agent produces.
+pub fn play_match(
+    agent_x: &mut dyn Agent,
+    agent_o: &mut dyn Agent,
+    start: GameState,
+) -> Result<(Vec<Action>, Score), Error> {
+    let mut state = start;
+    let mut actions = Vec::new();
+    loop {
+        match state.score() {
+            Score::Undecided => {}
+            score => return Ok((actions, score)),
+        }
+
+        let legal = state.legal_moves();
+        let Action::Put { mask } = if state.is_player1 {
+            agent_x.choose(&state, &legal)
+        } else {
+            agent_o.choose(&state, &legal)
+        };
+        legal.validate(mask)?;
+
+        state = state.perform(Action::Put { mask });
+        actions.push(Action::Put { mask });
+    }
+}
+
+/// Plays uniformly at random among the legal moves, the same policy as
+/// `StrategyRandom`, but owning its own RNG since `Agent::choose` isn't
+/// handed one.
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        RandomAgent { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose(&mut self, _state: &GameState, legal: &LegalMoves) -> Action {
+        let mut choice = self.rng.gen_range(0..legal.occupied.count_zeros());
+        let mut current = 1;
+        loop {
+            if (legal.occupied & current) == 0 {
+                if choice == 0 {
+                    return Action::Put { mask: current };
+                }
+                choice -= 1;
+            }
+            current *= 2;
+        }
+    }
+}
+
+/// Wraps the negamax engine (see `crate::ai`) as an `Agent`.
+pub struct NegamaxAgent {
+    rng: StdRng,
+    difficulty: AIDifficulty,
+}
+
+impl NegamaxAgent {
+    pub fn new(seed: u64, difficulty: AIDifficulty) -> Self {
+        NegamaxAgent { rng: StdRng::seed_from_u64(seed), difficulty }
+    }
+}
+
+impl Agent for NegamaxAgent {
+    fn choose(&mut self, state: &GameState, _legal: &LegalMoves) -> Action {
+        let (action, _) = ai::best_move(state, self.difficulty, &mut self.rng);
+        action
+    }
+}
+
+/// Win/draw/loss tally for one entrant of a `round_robin`.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Play every entrant against every other entrant `games_per_pairing` times
+/// as player1 and `games_per_pairing` times as player2, and report each
+/// entrant's aggregated win/draw/loss tally, so users can benchmark their
+/// own `Agent` implementations against the built-in ones.
+pub fn round_robin(
+    entrants: &mut [(String, Box<dyn Agent>)],
+    games_per_pairing: u32,
+) -> Result<HashMap<String, Tally>, Error> {
+    let mut tallies: HashMap<String, Tally> =
+        entrants.iter().map(|(name, _)| (name.clone(), Tally::default())).collect();
+
+    for i in 0..entrants.len() {
+        for j in (i + 1)..entrants.len() {
+            let (left, right) = entrants.split_at_mut(j);
+            let (name_x, agent_x) = &mut left[i];
+            let (name_o, agent_o) = &mut right[0];
+
+            for _ in 0..games_per_pairing {
+                let (_, score) = play_match(agent_x.as_mut(), agent_o.as_mut(), GameState::default())?;
+                record(&mut tallies, name_x, name_o, score);
+            }
+            for _ in 0..games_per_pairing {
+                // Swap roles so each entrant plays both sides of the pairing.
+                let (_, score) = play_match(agent_o.as_mut(), agent_x.as_mut(), GameState::default())?;
+                record(&mut tallies, name_o, name_x, score);
+            }
+        }
+    }
+
+    Ok(tallies)
+}
+
+/// Update `tallies` for a finished game between `name_x` (player1) and
+/// `name_o` (player2).
+fn record(tallies: &mut HashMap<String, Tally>, name_x: &str, name_o: &str, score: Score) {
+    match score {
+        Score::Player1Wins => {
+            tallies.get_mut(name_x).unwrap().wins += 1;
+            tallies.get_mut(name_o).unwrap().losses += 1;
+        }
+        Score::Player2Wins => {
+            tallies.get_mut(name_o).unwrap().wins += 1;
+            tallies.get_mut(name_x).unwrap().losses += 1;
+        }
+        Score::Draw => {
+            tallies.get_mut(name_x).unwrap().draws += 1;
+            tallies.get_mut(name_o).unwrap().draws += 1;
+        }
+        Score::Undecided => panic!("should not happen"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Grid;
+
+    /// Plays exactly the masks it was given, in order, regardless of legality
+    /// -- used to drive `play_match` into its illegal-move rejection path.
+    struct ScriptedAgent {
+        moves: std::vec::IntoIter<Grid>,
+    }
+
+    impl Agent for ScriptedAgent {
+        fn choose(&mut self, _state: &GameState, _legal: &LegalMoves) -> Action {
+            Action::Put { mask: self.moves.next().unwrap() }
+        }
+    }
+
+    #[test]
+    fn play_match_rejects_a_move_onto_an_occupied_cell() {
+        let mut scripted = ScriptedAgent { moves: vec![1 << 0, 1 << 0].into_iter() };
+        let mut random = RandomAgent::new(0);
+        let result = play_match(&mut scripted, &mut random, GameState::default());
+        assert!(matches!(result, Err(Error::IllegalMove(_))));
+    }
+
+    #[test]
+    fn play_match_returns_the_terminal_score_of_the_final_state() {
+        let mut hard = NegamaxAgent::new(0, AIDifficulty::Hard);
+        let mut random = RandomAgent::new(0);
+        let (actions, score) = play_match(&mut hard, &mut random, GameState::default()).unwrap();
+
+        let mut replayed = GameState::default();
+        for action in actions {
+            replayed = replayed.perform(action);
+        }
+        assert_eq!(replayed.score(), score);
+    }
+
+    #[test]
+    fn round_robin_hard_never_loses_to_random() {
+        let mut entrants: Vec<(String, Box<dyn Agent>)> = vec![
+            ("hard".into(), Box::new(NegamaxAgent::new(0, AIDifficulty::Hard))),
+            ("random".into(), Box::new(RandomAgent::new(1))),
+        ];
+        let tallies = round_robin(&mut entrants, 10).unwrap();
+        assert_eq!(tallies["hard"].losses, 0);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::types::{Action, GameState, Grid, Score};
+use rand::{Rng, RngCore};
+
+/// Selectable opponent strength: `Hard` always plays the game-theoretically
+/// best move, `Medium` usually does but sometimes slips in a random legal
+/// move, and `Easy` ignores the search entirely and plays uniformly at
+/// random.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AIDifficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// Chance, out of 100, that `Medium` plays the move `Hard` would have played
+/// rather than a random legal one.
+const MEDIUM_OPTIMAL_PCT: u32 = 80;
+
+fn value(mover_is_player1: bool, score: Score) -> i8 {
+    match (mover_is_player1, score) {
+        (true, Score::Player1Wins) | (false, Score::Player2Wins) => 1,
+        (_, Score::Player2Wins) | (_, Score::Player1Wins) => -1,
+        (_, Score::Draw) => 0,
+        (_, Score::Undecided) => panic!("should not happen"),
+    }
+}
+
+fn score_of(mover_is_player1: bool, value: i8) -> Score {
+    match (mover_is_player1, value) {
+        (true, 1) | (false, -1) => Score::Player1Wins,
+        (true, -1) | (false, 1) => Score::Player2Wins,
+        (_, 0) => Score::Draw,
+        _ => panic!("should not happen"),
+    }
+}
+
+/// Negamax with alpha-beta pruning, evaluated from the perspective of
+/// whichever player is to move in `state`: a forced win for the mover is
+/// `+1`, a forced loss is `-1`, a draw is `0`.  Because the tree is tiny (at
+/// most 5477 reachable states) this is always exact, never a heuristic
+/// estimate.
+fn negamax(state: &GameState, mut alpha: i8, beta: i8) -> i8 {
+    match state.score() {
+        Score::Undecided => {}
+        score => return value(state.is_player1, score),
+    }
+
+    let legal = state.legal_moves();
+    let mut best = -1;
+    for cell in 0..9 {
+        let mask: Grid = 1 << cell;
+        if legal.occupied & mask != 0 {
+            continue;
+        }
+        let next = state.perform(Action::Put { mask });
+        let v = -negamax(&next, -beta, -alpha);
+        best = best.max(v);
+        alpha = alpha.max(best);
+        if alpha >= beta {
+            // The other player already has a better option elsewhere; this
+            // branch cannot change the outcome, so stop exploring it.
+            break;
+        }
+    }
+    best
+}
+
+/// Pick a move for `state` according to `difficulty`, using `negamax` to
+/// find the game-theoretic value of the best legal move.  Moves searched
+/// after a better one was found are only searched far enough to show they
+/// do no better, so their recorded values are bounds, not exact values; the
+/// first move that reaches the best value is exact.  Returns the chosen
+/// `Action` together with the exact backed-up `Score` of `state` itself,
+/// regardless of which move `difficulty` ends up choosing, so callers can
+/// display e.g. "AI sees a forced draw" even while it plays a weaker move on
+/// purpose.
+pub fn best_move(state: &GameState, difficulty: AIDifficulty, rng: &mut dyn RngCore) -> (Action, Score) {
+    let legal = state.legal_moves();
+    let (mut alpha, beta) = (-1, 1);
+    let mut moves: Vec<(Grid, i8)> = Vec::new();
+    let mut best_value = -1;
+
+    for cell in 0..9 {
+        let mask: Grid = 1 << cell;
+        if legal.occupied & mask != 0 {
+            continue;
+        }
+        let next = state.perform(Action::Put { mask });
+        let v = -negamax(&next, -beta, -alpha);
+        best_value = best_value.max(v);
+        alpha = alpha.max(best_value);
+        moves.push((mask, v));
+    }
+
+    let score = score_of(state.is_player1, best_value);
+    let chosen = match difficulty {
+        AIDifficulty::Hard => moves.iter().find(|&&(_, v)| v == best_value).unwrap().0,
+        AIDifficulty::Medium if rng.gen_range(0..100) < MEDIUM_OPTIMAL_PCT => {
+            moves.iter().find(|&&(_, v)| v == best_value).unwrap().0
+        }
+        AIDifficulty::Medium | AIDifficulty::Easy => moves[rng.gen_range(0..moves.len())].0,
+    };
+
+    (Action::Put { mask: chosen }, score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn hard_difficulty_score_matches_the_exact_solver() {
+        let table = crate::solver::solve();
+        let state = GameState::default();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let (_, score) = best_move(&state, AIDifficulty::Hard, &mut rng);
+        assert_eq!(score, table[&state.canonical()]);
+    }
+
+    #[test]
+    fn hard_difficulty_takes_an_immediate_win() {
+        // X has 0 and 1, needs 2 to complete the top row; O has 3 and 4.
+        let mut state = GameState::default();
+        for cell in [0, 3, 1, 4] {
+            state = state.perform(Action::Put { mask: 1 << cell });
+        }
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let (action, score) = best_move(&state, AIDifficulty::Hard, &mut rng);
+        let Action::Put { mask } = action;
+        assert_eq!(mask, 1 << 2);
+        assert_eq!(score, Score::Player1Wins);
+    }
+}
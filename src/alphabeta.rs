@@ -1,73 +1,119 @@
 use crate::strategy::Strategy;
 use crate::types::{Action, GameState, Grid, Score};
-use rand::rngs::ThreadRng;
+use rand::RngCore;
 use std::collections::HashMap;
 
+fn value(score: Score) -> i8 {
+    match score {
+        Score::Player2Wins => -1,
+        Score::Draw => 0,
+        Score::Player1Wins => 1,
+        Score::Undecided => panic!("should not happen"),
+    }
+}
+
+fn score_of(value: i8) -> Score {
+    match value {
+        -1 => Score::Player2Wins,
+        0 => Score::Draw,
+        1 => Score::Player1Wins,
+        _ => panic!("should not happen"),
+    }
+}
+
 #[derive(Default)]
 pub struct StrategyAlphaBeta {
     cache: HashMap<GameState, (Score, Grid)>,
 }
 impl StrategyAlphaBeta {
-    fn play_with_score(&mut self, state: &GameState) -> (Score, Grid) {
-        // ??? Could go faster by checking symmetries and rotations
-        if let Some(b) = self.cache.get(state) {
-            return *b;
+    /// Minimax with alpha-beta pruning, keyed on the canonical form of the
+    /// state so that the ~5477 reachable states collapse to their symmetry
+    /// classes.  `alpha`/`beta` are the best value player1/player2 can
+    /// already guarantee elsewhere in the tree (Player2Wins < Draw <
+    /// Player1Wins); as soon as a branch cannot improve on that, the
+    /// remaining children are skipped.
+    fn play_with_score(&mut self, state: &GameState, mut alpha: i8, mut beta: i8) -> (Score, Grid) {
+        // A value computed under a narrowed (alpha, beta) window may only be
+        // a bound, not the exact minimax value (that's the whole point of
+        // the cutoff below): only caching what we find under the full
+        // (-1, 1) window keeps every entry exact, so a later call with a
+        // different window can always safely reuse it.
+        let full_window = alpha == -1 && beta == 1;
+
+        let (canonical, sym) = state.canonical_with_symmetry();
+        if let Some(&(score, mask)) = self.cache.get(&canonical) {
+            return (score, GameState::map_move_from_symmetry(mask, sym));
         }
 
-        let legal = state.legal_moves();
-        let mut best_score: Score = Score::Undecided;
-        let mut best_play_mask: Grid = 0;
+        let legal = canonical.legal_moves();
+        let mut best_value = if canonical.is_player1 { i8::MIN } else { i8::MAX };
+        let mut best_mask: Grid = 0;
+
         for current in 0..=8 {
             let mask = 1 << current;
-            if (legal.occupied & mask) == 0 {
-                let next_state = state.perform(Action::Put { mask });
-                let mut score = next_state.score();
-                if let Score::Undecided = score {
-                    let (s, _) = self.play_with_score(&next_state);
-                    score = s;
+            if (legal.occupied & mask) != 0 {
+                continue;
+            }
+            let next_state = canonical.perform(Action::Put { mask });
+            let next_value = match next_state.score() {
+                Score::Undecided => {
+                    let (s, _) = self.play_with_score(&next_state, alpha, beta);
+                    value(s)
                 }
+                s => value(s),
+            };
 
-                match (state.is_player1, score, best_score) {
-                    (true, Score::Player1Wins, _) => {
-                        return (Score::Player1Wins, mask);
-                    }
-                    (false, Score::Player1Wins, Score::Undecided) => {
-                        best_score = Score::Player1Wins;
-                        best_play_mask = mask;
-                    }
-                    (true, Score::Player2Wins, Score::Undecided) => {
-                        best_score = Score::Player2Wins;
-                        best_play_mask = mask;
-                    }
-                    (false, Score::Player2Wins, _) => {
-                        return (Score::Player2Wins, mask);
-                    }
-                    (true, Score::Draw, Score::Player2Wins)
-                    | (false, Score::Draw, Score::Player1Wins)
-                    | (_, Score::Draw, Score::Undecided) => {
-                        // draw is better than letting the other player win
-                        best_score = Score::Draw;
-                        best_play_mask = mask;
-                    }
-                    (false, Score::Player1Wins, _)
-                    | (true, Score::Player2Wins, _)
-                    | (_, Score::Draw, _) => {
-                        // We already have a better strategy, ignore this one
-                    }
-                    (_, Score::Undecided, _) => {
-                        panic!("should not happen");
-                    }
+            if canonical.is_player1 {
+                if next_value > best_value {
+                    best_value = next_value;
+                    best_mask = mask;
                 }
+                alpha = alpha.max(best_value);
+            } else {
+                if next_value < best_value {
+                    best_value = next_value;
+                    best_mask = mask;
+                }
+                beta = beta.min(best_value);
+            }
+            if alpha >= beta {
+                // The other player already has a better option elsewhere;
+                // this branch cannot change the outcome, so stop exploring it.
+                break;
             }
         }
-        self.cache.insert(*state, (best_score, best_play_mask));
-        (best_score, best_play_mask)
+
+        let best_score = score_of(best_value);
+        if full_window {
+            self.cache.insert(canonical, (best_score, best_mask));
+        }
+        (best_score, GameState::map_move_from_symmetry(best_mask, sym))
     }
 }
 
 impl Strategy for StrategyAlphaBeta {
-    fn play(&mut self, state: &GameState, _: &mut ThreadRng) -> Action {
-        let (_, m) = self.play_with_score(state);
+    fn name(&self) -> String {
+        "AlphaBeta".into()
+    }
+
+    fn play(&mut self, state: &GameState, _: &mut dyn RngCore) -> Action {
+        let (_, m) = self.play_with_score(state, -1, 1);
         Action::Put { mask: m }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_cached_score_agrees_with_the_exact_solver() {
+        let mut strategy = StrategyAlphaBeta::default();
+        strategy.play_with_score(&GameState::default(), -1, 1);
+
+        let table = crate::solver::solve();
+        for (&canonical, &(score, _)) in &strategy.cache {
+            assert_eq!(score, table[&canonical], "mismatch for:\n{}", canonical);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::types::Grid;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownStrategy(String),
+    InvalidArgument(String),
+    IllegalMove(Grid),
+    NotYourTurn,
+    GameInProgress,
+    NoGame,
+    CannotJoinOwnGame,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownStrategy(name) => {
+                write!(f, "unknown strategy {:?} (expected one of: random, alphabeta, montecarlo)", name)
+            }
+            Error::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            Error::IllegalMove(mask) => {
+                write!(f, "illegal move: mask {:#b} is occupied or not a single on-board cell", mask)
+            }
+            Error::NotYourTurn => write!(f, "it is not your turn"),
+            Error::GameInProgress => write!(f, "the game is not in a state that allows this action"),
+            Error::NoGame => write!(f, "no game with that id"),
+            Error::CannotJoinOwnGame => write!(f, "a player cannot join their own game as the opponent"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,178 @@
+use crate::errors::Error;
+use crate::types::{Action, GameState, Grid, Score};
+use std::collections::HashMap;
+
+/// A player handle, as supplied by whatever transport sits in front of the
+/// lobby (a socket, a websocket session, ...).
+pub type PlayerId = String;
+
+/// A game's place in its lifecycle, mirroring the Solana tic-tac-toe
+/// program's state machine: a game starts `WaitingForO`, a second player
+/// `join`s (`ORequestPending`), the creator `accept`s them in
+/// (`XMove`), and turns alternate until the position is terminal
+/// (`Finished`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    WaitingForO,
+    ORequestPending,
+    XMove,
+    OMove,
+    Finished(Score),
+}
+
+/// Wraps the pure `GameState`/`perform`/`score` core with player identities
+/// and turn-order enforcement suitable for a server: `create`, `join`,
+/// `accept` and `make_move` are the only ways to mutate a `Game`.  `join`
+/// and `make_move` reject the wrong player and the wrong point in `status`;
+/// `accept` takes no player, so it only checks `status` and does not check
+/// who is accepting.
+pub struct Game {
+    pub player_x: PlayerId,
+    pub player_o: Option<PlayerId>,
+    pub status: GameStatus,
+    pub state: GameState,
+}
+
+impl Game {
+    /// Start a new game waiting for a second player, with `player_x` as its
+    /// creator.
+    pub fn create(player_x: PlayerId) -> Self {
+        Game {
+            player_x,
+            player_o: None,
+            status: GameStatus::WaitingForO,
+            state: GameState::default(),
+        }
+    }
+
+    /// `player` asks to join this game as O.  Only valid while the game is
+    /// still `WaitingForO`; the creator still has to `accept` before play
+    /// starts.  `player` cannot be `player_x`: one identity is not allowed
+    /// to play both sides of the same game.
+    pub fn join(&mut self, player: PlayerId) -> Result<(), Error> {
+        if self.status != GameStatus::WaitingForO {
+            return Err(Error::GameInProgress);
+        }
+        if player == self.player_x {
+            return Err(Error::CannotJoinOwnGame);
+        }
+        self.player_o = Some(player);
+        self.status = GameStatus::ORequestPending;
+        Ok(())
+    }
+
+    /// The creator accepts the pending join request, starting play with X
+    /// (the creator) to move first.
+    pub fn accept(&mut self) -> Result<(), Error> {
+        if self.status != GameStatus::ORequestPending {
+            return Err(Error::GameInProgress);
+        }
+        self.status = GameStatus::XMove;
+        Ok(())
+    }
+
+    /// `player` plays `mask` if it is their turn, validating it against
+    /// `legal_moves()` the same way `agent::play_match` validates an
+    /// `Agent`'s move, then advances `status` to the next mover or to
+    /// `Finished` if the move ended the game.
+    pub fn make_move(&mut self, player: &str, mask: Grid) -> Result<(), Error> {
+        let expected = match self.status {
+            GameStatus::XMove => &self.player_x,
+            GameStatus::OMove => self.player_o.as_ref().ok_or(Error::GameInProgress)?,
+            GameStatus::WaitingForO | GameStatus::ORequestPending | GameStatus::Finished(_) => {
+                return Err(Error::GameInProgress);
+            }
+        };
+        if expected != player {
+            return Err(Error::NotYourTurn);
+        }
+
+        self.state.legal_moves().validate(mask)?;
+
+        self.state = self.state.perform(Action::Put { mask });
+        self.status = match self.state.score() {
+            Score::Undecided => {
+                if self.state.is_player1 {
+                    GameStatus::XMove
+                } else {
+                    GameStatus::OMove
+                }
+            }
+            score => GameStatus::Finished(score),
+        };
+        Ok(())
+    }
+}
+
+pub type GameId = u64;
+
+/// Holds every game the server is tracking, keyed by `GameId`, so a
+/// dashboard can list many concurrent games instead of the single in-memory
+/// match the CLI tournament plays.
+#[derive(Default)]
+pub struct Lobby {
+    games: HashMap<GameId, Game>,
+    next_id: GameId,
+}
+
+impl Lobby {
+    pub fn create(&mut self, player_x: PlayerId) -> GameId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.games.insert(id, Game::create(player_x));
+        id
+    }
+
+    pub fn join(&mut self, id: GameId, player: PlayerId) -> Result<(), Error> {
+        self.games.get_mut(&id).ok_or(Error::NoGame)?.join(player)
+    }
+
+    pub fn accept(&mut self, id: GameId) -> Result<(), Error> {
+        self.games.get_mut(&id).ok_or(Error::NoGame)?.accept()
+    }
+
+    pub fn make_move(&mut self, id: GameId, player: &str, mask: Grid) -> Result<(), Error> {
+        self.games.get_mut(&id).ok_or(Error::NoGame)?.make_move(player, mask)
+    }
+
+    pub fn get(&self, id: GameId) -> Result<&Game, Error> {
+        self.games.get(&id).ok_or(Error::NoGame)
+    }
+
+    /// IDs of games still waiting for a second player to join.
+    pub fn open_games(&self) -> impl Iterator<Item = GameId> + '_ {
+        self.games
+            .iter()
+            .filter(|(_, game)| game.status == GameStatus::WaitingForO)
+            .map(|(&id, _)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_rejects_the_creator_joining_their_own_game() {
+        let mut game = Game::create("alice".into());
+        assert!(matches!(game.join("alice".into()), Err(Error::CannotJoinOwnGame)));
+    }
+
+    #[test]
+    fn make_move_rejects_the_wrong_player() {
+        let mut game = Game::create("alice".into());
+        game.join("bob".into()).unwrap();
+        game.accept().unwrap();
+        assert!(matches!(game.make_move("bob", 1), Err(Error::NotYourTurn)));
+    }
+
+    #[test]
+    fn make_move_rejects_the_wrong_phase() {
+        let mut game = Game::create("alice".into());
+        // Nobody can move before O has joined and X has accepted.
+        assert!(matches!(game.make_move("alice", 1), Err(Error::GameInProgress)));
+
+        game.join("bob".into()).unwrap();
+        assert!(matches!(game.make_move("bob", 1), Err(Error::GameInProgress)));
+    }
+}
@@ -1,229 +1,117 @@
+mod agent;
+mod ai;
+mod alphabeta;
 mod errors;
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use std::collections::HashMap;
-use std::thread::available_parallelism;
-
-#[derive(Debug)]
-enum Action {
-    Put { mask: u16 },
-}
-
-#[derive(Clone, Copy, Debug)]
-enum Score {
-    Player1Wins,
-    Player2Wins,
-    Draw,
-    Unknown,
-}
-
-struct LegalMoves {
-    occupied: u16, //  each bit is whether the corresponding cell is legal
-}
+mod lobby;
+mod mcts;
+mod random;
+mod record;
+mod solver;
+mod strategy;
+mod types;
+mod wire;
 
-trait Strategy {
-    fn play(&mut self, state: &GameState, rng: &mut ThreadRng) -> Action;
-}
-
-struct StrategyRandom {}
-impl Strategy for StrategyRandom {
-    fn play(&mut self, state: &GameState, rng: &mut ThreadRng) -> Action {
-        let legal = state.legal_moves();
-        let mut choice = rng.gen_range(0..legal.occupied.count_zeros());
-        let mut current = 1;
-        loop {
-            if (legal.occupied & current) == 0 {
-                if choice == 0 {
-                    return Action::Put { mask: current };
-                }
-                choice -= 1;
-            }
-            current *= 2;
-        }
-    }
-}
+use crate::agent::{Agent, NegamaxAgent, RandomAgent};
+use crate::ai::AIDifficulty;
+use crate::alphabeta::StrategyAlphaBeta;
+use crate::errors::Error;
+use crate::lobby::{GameStatus, Lobby};
+use crate::mcts::StrategyMCTS;
+use crate::random::StrategyRandom;
+use crate::record::GameRecord;
+use crate::strategy::Strategy;
+use crate::types::{Action, GameState, Score};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::thread::available_parallelism;
 
-#[derive(Default)]
-struct StrategyAlphaBeta {
-    cache: HashMap<GameState, (Score, u16)>,
+/// Configuration for one run of the tournament: which strategy plays
+/// player1 and player2, how many games to play in total, the master RNG
+/// seed (for reproducibility), and how many worker threads to spread the
+/// games over.
+struct Config {
+    player1: String,
+    player2: String,
+    games: u32,
+    seed: u64,
+    threads: u32,
 }
-impl StrategyAlphaBeta {
-    fn play_with_score(&mut self, state: &GameState) -> (Score, u16) {
-        // ??? Could go faster by checking symmetries and rotations
-        if let Some(b) = self.cache.get(state) {
-            return *b;
-        }
-
-        let legal = state.legal_moves();
-        let mut best_score: Score = Score::Unknown;
-        let mut best_play_mask: u16 = 0;
-        for current in 0..=8 {
-            let mask = 1 << current;
-            if (legal.occupied & mask) == 0 {
-                let next_state = state.perform(Action::Put { mask });
-                let mut score = next_state.score();
-                if let Score::Unknown = score {
-                    let (s, _) = self.play_with_score(&next_state);
-                    score = s;
-                }
 
-                match (state.is_player1, score, best_score) {
-                    (true, Score::Player1Wins, _) => {
-                        return (Score::Player1Wins, mask);
-                    }
-                    (false, Score::Player1Wins, Score::Unknown) => {
-                        best_score = Score::Player1Wins;
-                        best_play_mask = mask;
-                    }
-                    (true, Score::Player2Wins, Score::Unknown) => {
-                        best_score = Score::Player2Wins;
-                        best_play_mask = mask;
-                    }
-                    (false, Score::Player2Wins, _) => {
-                        return (Score::Player2Wins, mask);
-                    }
-                    (true, Score::Draw, Score::Player2Wins)
-                    | (false, Score::Draw, Score::Player1Wins)
-                    | (_, Score::Draw, Score::Unknown) => {
-                        // draw is better than letting the other player win
-                        best_score = Score::Draw;
-                        best_play_mask = mask;
-                    }
-                    (false, Score::Player1Wins, _)
-                    | (true, Score::Player2Wins, _)
-                    | (_, Score::Draw, _) => {
-                        // We already have a better strategy, ignore this one
-                    }
-                    (_, Score::Unknown, _) => {
-                        panic!("should not happen");
-                    }
-                }
-            }
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            player1: "random".into(),
+            player2: "alphabeta".into(),
+            games: 1_000_000,
+            seed: 0,
+            threads: available_parallelism().unwrap().get() as u32,
         }
-        self.cache.insert(*state, (best_score, best_play_mask));
-        (best_score, best_play_mask)
     }
 }
 
-impl Strategy for StrategyAlphaBeta {
-    fn play(&mut self, state: &GameState, _: &mut ThreadRng) -> Action {
-        let (_, m) = self.play_with_score(state);
-        Action::Put { mask: m }
-    }
+fn next_arg(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<String, Error> {
+    args.next()
+        .ok_or_else(|| Error::InvalidArgument(format!("{} expects a value", flag)))
 }
 
-#[derive(Clone, Copy, Eq, Hash, PartialEq)]
-struct GameState {
-    player1: u16,     // bit set to 1 if player1 occupies the cell
-    player2: u16,     // bit set to 1 if player1 occupies the cell
-    is_player1: bool, // true if next to play is player1
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidArgument(format!("{} expects a number, got {:?}", flag, value)))
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        GameState {
-            player1: !0b111111111, // so that count_zeros only looks at board
-            player2: !0b111111111,
-            is_player1: true,
-        }
-    }
-}
-
-impl GameState {
-    pub fn perform(&self, action: Action) -> Self {
-        match action {
-            Action::Put { mask } => {
-                if self.is_player1 {
-                    GameState {
-                        player1: self.player1 | mask,
-                        player2: self.player2,
-                        is_player1: false,
-                    }
-                } else {
-                    GameState {
-                        player1: self.player1,
-                        player2: self.player2 | mask,
-                        is_player1: true,
-                    }
-                }
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Config, Error> {
+    let mut config = Config::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-x" => config.player1 = next_arg(&mut args, "-x")?,
+            "-o" => config.player2 = next_arg(&mut args, "-o")?,
+            "-n" => config.games = parse_number("-n", &next_arg(&mut args, "-n")?)?,
+            "-s" => config.seed = parse_number("-s", &next_arg(&mut args, "-s")?)?,
+            "-t" => config.threads = parse_number("-t", &next_arg(&mut args, "-t")?)?,
+            other => {
+                return Err(Error::InvalidArgument(format!("unknown option {:?}", other)))
             }
         }
     }
-
-    pub fn legal_moves(&self) -> LegalMoves {
-        LegalMoves {
-            occupied: self.player1 | self.player2,
-        }
-    }
-
-    pub fn score(&self) -> Score {
-        if self.player1 & 0b000000111 == 0b000000111
-            || self.player1 & 0b000111000 == 0b000111000
-            || self.player1 & 0b111000000 == 0b111000000
-            || self.player1 & 0b100100100 == 0b100100100
-            || self.player1 & 0b010010010 == 0b010010010
-            || self.player1 & 0b001001001 == 0b001001001
-            || self.player1 & 0b100010001 == 0b100010001
-            || self.player1 & 0b001010100 == 0b001010100
-        {
-            Score::Player1Wins
-        } else if self.player2 & 0b000000111 == 0b000000111
-            || self.player2 & 0b000111000 == 0b000111000
-            || self.player2 & 0b111000000 == 0b111000000
-            || self.player2 & 0b100100100 == 0b100100100
-            || self.player2 & 0b010010010 == 0b010010010
-            || self.player2 & 0b001001001 == 0b001001001
-            || self.player2 & 0b100010001 == 0b100010001
-            || self.player2 & 0b001010100 == 0b001010100
-        {
-            Score::Player2Wins
-        } else if (self.player1 | self.player2) == !0 {
-            Score::Draw
-        } else {
-            Score::Unknown
-        }
-    }
+    Ok(config)
 }
 
-impl std::fmt::Display for GameState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fn img(state: &GameState, bit: u16) -> char {
-            if state.player1 & bit != 0 {
-                'X'
-            } else if state.player2 & bit != 0 {
-                'O'
-            } else {
-                '.'
-            }
-        }
-        writeln!(f, "{:?}", self.score())?;
-        writeln!(f, "{} {} {}", img(self, 1), img(self, 2), img(self, 4))?;
-        writeln!(f, "{} {} {}", img(self, 8), img(self, 16), img(self, 32))?;
-        writeln!(f, "{} {} {}", img(self, 64), img(self, 128), img(self, 256))?;
-        Ok(())
+/// Build the strategy named on the command-line (`random`, `alphabeta` or
+/// `montecarlo`) behind a trait object, so player1 and player2 can be any
+/// combination chosen at runtime.
+fn make_strategy(name: &str) -> Result<Box<dyn Strategy>, Error> {
+    match name {
+        "random" => Ok(Box::new(StrategyRandom::default())),
+        "alphabeta" => Ok(Box::new(StrategyAlphaBeta::default())),
+        "montecarlo" => Ok(Box::new(StrategyMCTS::default())),
+        other => Err(Error::UnknownStrategy(other.into())),
     }
 }
 
-fn play<Strategy1: Strategy, Strategy2: Strategy>(
+fn play(
     max_count: u32,
-    player1: &mut Strategy1,
-    player2: &mut Strategy2,
+    player1: &mut dyn Strategy,
+    player2: &mut dyn Strategy,
+    rng: &mut dyn RngCore,
 ) -> (u32, u32, u32, u32) {
     let mut play1wins = 0;
     let mut play2wins = 0;
     let mut draw = 0;
     let mut played = 0;
-    let mut rng = rand::thread_rng();
 
-    loop {
+    while played < max_count {
         let mut state = GameState::default();
         played += 1;
 
         loop {
             let action = if state.is_player1 {
-                player1.play(&state, &mut rng)
+                player1.play(&state, rng)
             } else {
-                player2.play(&state, &mut rng)
+                player2.play(&state, rng)
             };
 
             state = state.perform(action);
@@ -240,40 +128,229 @@ fn play<Strategy1: Strategy, Strategy2: Strategy>(
                     draw += 1;
                     break;
                 }
-                Score::Unknown => {}
+                Score::Undecided => {}
             }
         }
+    }
+    (played, play1wins, play2wins, draw)
+}
+
+/// `solve` subcommand: solve the whole game with `solver::solve` and report
+/// the perfect-play outcome from the opening position, plus how many
+/// essentially-distinct positions that took.
+fn run_solve() -> Result<(), Error> {
+    let table = solver::solve();
+    let outcome = table[&GameState::default().canonical()];
+    println!(
+        "{} canonical positions solved; perfect play from the opening position: {:?}",
+        table.len(),
+        outcome
+    );
+    Ok(())
+}
 
-        if played >= max_count {
+/// `match` subcommand: benchmark the three `AIDifficulty` tiers and
+/// `RandomAgent` against each other with `agent::round_robin`, exercising
+/// the external `Agent` ABI end to end.
+fn run_match() -> Result<(), Error> {
+    let mut entrants: Vec<(String, Box<dyn Agent>)> = vec![
+        ("hard".into(), Box::new(NegamaxAgent::new(0, AIDifficulty::Hard))),
+        ("medium".into(), Box::new(NegamaxAgent::new(1, AIDifficulty::Medium))),
+        ("easy".into(), Box::new(NegamaxAgent::new(2, AIDifficulty::Easy))),
+        ("random".into(), Box::new(RandomAgent::new(3))),
+    ];
+    let tallies = agent::round_robin(&mut entrants, 50)?;
+    for (name, _) in &entrants {
+        println!("{}: {:?}", name, tallies[name]);
+    }
+    Ok(())
+}
+
+/// `lobby` subcommand: walk one `Lobby` game through its full lifecycle
+/// (`create`, `join`, `accept`, alternating `make_move`s to a finish),
+/// printing each status transition.  A second game is left waiting for O so
+/// that `Lobby::open_games` has something to report.
+fn run_lobby_demo() -> Result<(), Error> {
+    let mut lobby = Lobby::default();
+    let id = lobby.create("alice".into());
+    println!("created game {id}, status {:?}", lobby.get(id)?.status);
+
+    let waiting_id = lobby.create("carol".into());
+    println!(
+        "open games (waiting for O): {:?}",
+        lobby.open_games().collect::<Vec<_>>()
+    );
+
+    lobby.join(id, "bob".into())?;
+    println!("bob joined, status {:?}", lobby.get(id)?.status);
+    println!(
+        "open games (waiting for O): {:?}",
+        lobby.open_games().collect::<Vec<_>>()
+    );
+
+    lobby.accept(id)?;
+    println!("alice accepted, status {:?}", lobby.get(id)?.status);
+
+    let mut turn = "alice";
+    for cell in [4, 0, 1, 7, 2, 6, 5, 3, 8] {
+        if matches!(lobby.get(id)?.status, GameStatus::Finished(_)) {
             break;
         }
+        lobby.make_move(id, turn, 1 << cell)?;
+        turn = if turn == "alice" { "bob" } else { "alice" };
     }
-    (played, play1wins, play2wins, draw)
+    println!("final status: {:?}", lobby.get(id)?.status);
+    println!(
+        "still waiting for O: {:?} ({waiting_id} still open)",
+        lobby.open_games().collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+/// `record` subcommand: play a short game into a `GameRecord`, auto-annotate
+/// it against `solver::solve`, then replay the mainline back out.
+fn run_record_demo() -> Result<(), Error> {
+    let table = solver::solve();
+    let mut record = GameRecord { start: GameState::default(), variations: Vec::new() };
+
+    let mut state = record.start;
+    let mut cursor = &mut record.variations;
+    for (idx, cell) in [4, 0, 8, 2, 6].into_iter().enumerate() {
+        let mask = 1 << cell;
+        state = state.perform(Action::Put { mask });
+        cursor.push(record::Node {
+            action: Action::Put { mask },
+            state,
+            comment: if idx == 0 { Some("opening: take the center".into()) } else { None },
+            evaluation: None,
+            annotation: None,
+            variations: Vec::new(),
+        });
+        cursor = &mut cursor.last_mut().unwrap().variations;
+        if state.score() != Score::Undecided {
+            break;
+        }
+    }
+
+    record.auto_annotate(&table);
+    for node in record.replay() {
+        println!(
+            "{:?} -> eval {:?}, annotation {:?}{}",
+            node.action,
+            node.evaluation,
+            node.annotation,
+            node.comment.as_deref().map(|c| format!(" ({c})")).unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+/// `mcts` subcommand: play a short game against `StrategyMCTS` used through
+/// its concrete type (rather than behind `dyn Strategy`), so that
+/// `with_node_cap` and `last_simulations` are actually exercised, logging
+/// the search effort spent on each of its moves.
+fn run_mcts_demo() -> Result<(), Error> {
+    let mut mcts = StrategyMCTS::new(std::time::Duration::from_millis(50)).with_node_cap(1_000);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut state = GameState::default();
+
+    loop {
+        let action = mcts.play(&state, &mut rng);
+        println!(
+            "{:?} after {} simulations",
+            action,
+            mcts.last_simulations()
+        );
+        state = state.perform(action);
+        if state.score() != Score::Undecided {
+            break;
+        }
+
+        let action = StrategyRandom::default().play(&state, &mut rng);
+        state = state.perform(action);
+        if state.score() != Score::Undecided {
+            break;
+        }
+    }
+    println!("final score: {:?}", state.score());
+    Ok(())
+}
+
+/// `cbor` subcommand: round-trip a `GameState` through `wire`'s CBOR
+/// encoding, the format the Solana tic-tac-toe program itself uses.
+#[cfg(feature = "serde")]
+fn run_cbor_demo() -> Result<(), Error> {
+    let state = GameState::default().perform(Action::Put { mask: 1 << 4 });
+    let bytes = state.to_cbor();
+    let round_tripped =
+        GameState::from_cbor(&bytes).map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    println!("round-tripped {} bytes of CBOR; matches = {}", bytes.len(), state == round_tripped);
+    Ok(())
 }
 
-fn main() -> Result<(), crate::errors::Error> {
-    const TOTAL_RUNS: u32 = 1_000_000;
-    let cores: u32 = available_parallelism().unwrap().get().try_into().unwrap();
+fn main() -> Result<(), Error> {
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("solve") => {
+            args.next();
+            return run_solve();
+        }
+        Some("match") => {
+            args.next();
+            return run_match();
+        }
+        Some("lobby") => {
+            args.next();
+            return run_lobby_demo();
+        }
+        Some("record") => {
+            args.next();
+            return run_record_demo();
+        }
+        Some("mcts") => {
+            args.next();
+            return run_mcts_demo();
+        }
+        #[cfg(feature = "serde")]
+        Some("cbor") => {
+            args.next();
+            return run_cbor_demo();
+        }
+        Some(other) if !other.starts_with('-') => {
+            return Err(Error::InvalidArgument(format!(
+                "unknown subcommand {:?} (expected one of: solve, match, lobby, record, mcts, cbor, or a tournament flag like -x)",
+                other
+            )));
+        }
+        _ => {}
+    }
+    run_tournament(args)
+}
 
-    // https://math.stackexchange.com/questions/4045893/if-two-computers-are-playing-tic-tac-toe-but-they-are-choosing-their-squares-ra
-    // Random vs Random:
-    //    theory says 58.49% of wins for player1, 28.81% for player1,
-    //    and 12.70% draw.
-    // Random vs Perfect:
-    //    if first player is perfect:
-    //       wins with 191/192 = 99.48% of wins
-    //       draws with 1/192 = 0.52%
-    //    if second player is perfect:
-    //       wins with 887/945 = 93.86%
-    //       draws with 43/945 =  4.55%
-    //       loses with 1/945  =  1.06%
+fn run_tournament(args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let config = parse_args(args)?;
 
-    let handles = (0..cores)
-        .map(|_| {
-            std::thread::spawn(move || {
-                let mut player1 = StrategyRandom {};
-                let mut player2 = StrategyAlphaBeta::default();
-                play(TOTAL_RUNS / cores, &mut player1, &mut player2)
+    // Each thread gets its own deterministic RNG, seeded from the master
+    // seed plus the thread index, so that the reported win rates (and any
+    // individual game within them) are exactly reproducible across runs
+    // for `random` and `alphabeta`.  `montecarlo` stops each search on a
+    // wall-clock budget, so its games can differ from run to run.
+    let handles = (0..config.threads)
+        .map(|idx| {
+            let player1_name = config.player1.clone();
+            let player2_name = config.player2.clone();
+            // Give the remainder of the division to the first few threads so
+            // the games actually played always add up to exactly
+            // config.games, instead of every thread's own do-while floor
+            // silently inflating the total (e.g. -n 5 -t 16 playing 16).
+            let games = config.games / config.threads
+                + u32::from(idx < config.games % config.threads);
+            let seed = config.seed.wrapping_add(idx as u64);
+            std::thread::spawn(move || -> Result<(u32, u32, u32, u32), Error> {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut player1 = make_strategy(&player1_name)?;
+                let mut player2 = make_strategy(&player2_name)?;
+                Ok(play(games, player1.as_mut(), player2.as_mut(), &mut rng))
             })
         })
         .collect::<Vec<_>>();
@@ -283,16 +360,24 @@ fn main() -> Result<(), crate::errors::Error> {
     let mut p2 = 0;
     let mut draw = 0;
     for h in handles {
-        let (tp, tp1, tp2, td) = h.join().unwrap();
+        let (tp, tp1, tp2, td) = h.join().unwrap()?;
         played += tp;
         p1 += tp1;
         p2 += tp2;
         draw += td;
     }
 
+    if played == 0 {
+        println!("0 games, {} vs {}, seed {}: nothing played", config.player1, config.player2, config.seed);
+        return Ok(());
+    }
+
     println!(
-        "total {} play1 {:.2}%, play2 {:.2}%, draw {:.2}%",
+        "{} games, {} vs {}, seed {}: player1 {:.2}%, player2 {:.2}%, draw {:.2}%",
         played,
+        config.player1,
+        config.player2,
+        config.seed,
         p1 as f32 / played as f32 * 100.,
         p2 as f32 / played as f32 * 100.,
         draw as f32 / played as f32 * 100.
@@ -1,10 +1,23 @@
+use crate::random::StrategyRandom;
 use crate::strategy::Strategy;
 use crate::types::{Action, GameState, Score};
-use rand::rngs::ThreadRng;
+use rand::RngCore;
 use rand::seq::SliceRandom;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Hard cap on the visit count of the node `play` searches from, set to the
+/// total number of valid tic-tac-toe states: once that node has been visited
+/// this many times, `play` stops searching even if time is left.  Visits
+/// count simulations, not distinct states, so reaching the cap does not mean
+/// every line below the node has been expanded.
+const MAX_VISITS: u32 = 5477;
+
+/// How many simulations to run between two calls to `Instant::now()`, so
+/// that checking the clock does not dominate the cost of a cheap simulation.
+const CLOCK_CHECK_INTERVAL: u32 = 64;
 
 /// The game is represented as a tree of nodes, but the nodes are only created
 /// as they are visited (so that if we had a large branching factor, we limit
@@ -14,15 +27,18 @@ use std::rc::Rc;
 ///
 /// In practice, tic-tac-toe only has 5477 valid states (out of 19683 different
 /// positions), so it is advantageous to cache those states in a hash map, and
-/// share the tree nodes.
-/// In terms of MCTS, it means that a given node will in general have multiple
-/// parents, and when we compute the "score" for a node, we should take into
-/// account the total number of times any of the parents was visited.
+/// share the tree nodes.  In terms of MCTS, it means that a given node will in
+/// general have multiple parents, possibly reached while searching from
+/// either player's root.  `wins`/`visited` are therefore always tracked
+/// relative to the player to move *in that node's own state* (negamax-style),
+/// never relative to a fixed root player: this is the only way the statistics
+/// stay meaningful no matter which parent led here.
 
 struct Node {
-    visited: u32,   // Number of times the node was visited
-    wins: u32,      // Number of times this node lead to a winning game.
-    moves: [Option<Rc<RefCell<Node>>>; 9],  // Each of the valid child nodes
+    visited: u32, // Number of times the node was visited
+    wins: u32,    // Number of playouts, from among those, won (or drawn) by
+                  // whichever player is to move in this node's state.
+    moves: [Option<Rc<RefCell<Node>>>; 9], // Each of the valid child nodes
 }
 const NOT_EXPLORED: Option<Rc<RefCell<Node>>> = None;
 
@@ -42,117 +58,239 @@ impl std::fmt::Debug for Node {
     }
 }
 
-#[derive(Default)]
+/// Default cap on the number of cached states (see `StrategyMCTS::node_cap`).
+/// Generous for tic-tac-toe (5477 reachable states total), but this is what
+/// keeps memory bounded once the engine is used on a larger board.
+const DEFAULT_NODE_CAP: usize = 50_000;
+
 pub struct StrategyMCTS {
     tree: HashMap<GameState, Rc<RefCell<Node>>>,
+    max_time: Duration,
+    last_simulations: u32,
+    node_cap: usize,
+}
+
+impl Default for StrategyMCTS {
+    fn default() -> Self {
+        StrategyMCTS::new(Duration::from_millis(500))
+    }
 }
 
 impl StrategyMCTS {
-    /// Returns 1 if full game was a win, 0 otherwise
-    fn search_one(
-        &mut self,
-        state: &GameState,
-        current_player_is_1: bool,
-        rng: &mut ThreadRng,
-    ) -> u32 {
-        let node = self.tree[state].clone();
-        let parent_visits = node.borrow().visited;
+    /// Create a strategy that searches for at most `max_time` on each call
+    /// to `play`, rather than a fixed number of iterations.  This makes it
+    /// usable under a real turn clock: it keeps searching until either the
+    /// budget is spent or the position's node reaches `MAX_VISITS` visits.
+    pub fn new(max_time: Duration) -> Self {
+        StrategyMCTS {
+            tree: HashMap::new(),
+            max_time,
+            last_simulations: 0,
+            node_cap: DEFAULT_NODE_CAP,
+        }
+    }
 
-        // Choose one child
-        // We implement the UCBT algorithm: for each child node, we compute
-        //    ucb1 = wins/visited + c * sqrt( ln(parent_visits) / visited)
-        // The first term encourages nodes that have had a better win ratio.
-        // The second term encourages nodes that haven't been visited in a
-        // while.
-        // We select the child with the highest ucb1
+    /// Set the maximum number of cached states to retain across moves (see
+    /// `prune`).
+    pub fn with_node_cap(mut self, node_cap: usize) -> Self {
+        self.node_cap = node_cap;
+        self
+    }
 
-        let legal = state.legal_moves();
-        let mut best = (f32::NEG_INFINITY, 0);
+    /// Number of simulations performed during the last call to `play`, so
+    /// that callers can log how much search effort went into a move.
+    pub fn last_simulations(&self) -> u32 {
+        self.last_simulations
+    }
 
-        // Shuffle things, so that in case of equality we do not always use
-        // the first choice available.
-        let mut vec: Vec<usize> = (0..9).collect();
-        vec.shuffle(rng);
+    /// Whether `state` could still occur from `root` onward, i.e. every
+    /// cell `root` already has filled is filled the same way in `state`.
+    fn is_reachable_from(state: &GameState, root: &GameState) -> bool {
+        root.player1 & state.player1 == root.player1
+            && root.player2 & state.player2 == root.player2
+    }
 
-        for idx in vec {
-            if (legal.occupied & (1 << idx)) != 0 {
-                // position is already occupied
-                continue;
-            }
+    /// Carry the tree forward across moves instead of discarding it: drop
+    /// every cached state that is no longer reachable from `root` (the
+    /// actual board now that moves have been played), then, if there are
+    /// still more than `node_cap` entries left, evict the least-visited
+    /// ones until the cap is met.  This keeps warm statistics for the live
+    /// subtree while giving a hard memory ceiling.
+    ///
+    /// `root` itself is never a candidate for eviction: `play` looks it up
+    /// (or inserts it blank) right after `prune` returns, so evicting it
+    /// here would throw away the very statistics that call is about to
+    /// build on, on the turn they matter most.
+    fn prune(&mut self, root: &GameState) {
+        self.tree.retain(|state, _| Self::is_reachable_from(state, root));
+
+        if self.tree.len() > self.node_cap {
+            let mut by_visits: Vec<(GameState, u32)> = self
+                .tree
+                .iter()
+                .filter(|(state, _)| *state != root)
+                .map(|(state, node)| (*state, node.borrow().visited))
+                .collect();
+            by_visits.sort_by_key(|&(_, visited)| visited);
 
-            let ucb1 = match &node.borrow().moves[idx] {
-                None => {
-                    // never visited
-                    f32::INFINITY
+            let evict_count = self.tree.len() - self.node_cap;
+            let mut evicted: HashSet<*const RefCell<Node>> =
+                HashSet::with_capacity(evict_count);
+            for (state, _) in by_visits.iter().take(evict_count) {
+                if let Some(node) = self.tree.remove(state) {
+                    evicted.insert(Rc::as_ptr(&node));
                 }
-                Some(child_node) => {
-                    // We might arrive at the same position via different
-                    // parents.  In this case, it is possible that the positions
-                    // "visited" is non-zero, but the "parent_visits" is zero
-                    let c = child_node.borrow();
-                    if parent_visits == 0 {
-                        //  ??? Should use the sum of the parent's visits
-                        f32::INFINITY
-                    } else {
-                        c.wins as f32 / c.visited as f32
-                            + 1.4
-                                * ((parent_visits as f32).ln()
-                                    / c.visited as f32)
-                                    .sqrt()
+            }
+
+            // A surviving node may still hold a `moves[idx]` pointing at one
+            // of the nodes we just evicted: reset it to unexplored so later
+            // search re-expands it (with fresh stats) instead of failing to
+            // find it in `tree` by its state.
+            for node in self.tree.values() {
+                for slot in node.borrow_mut().moves.iter_mut() {
+                    if slot.as_ref().is_some_and(|child| evicted.contains(&Rc::as_ptr(child))) {
+                        *slot = None;
                     }
                 }
-            };
-            if ucb1 > best.0 {
-                best = (ucb1, idx);
             }
         }
+    }
 
-        // Explore that child
-        let action = Action::Put { mask: 1 << best.1 };
+    /// Returns 1 if `score` is a win (or a draw) for whichever player is
+    /// `mover_is_player1`, 0 otherwise.  This is how a terminal outcome is
+    /// turned into the `wins` increment for a specific node, since each
+    /// node's stats are relative to the player to move in its own state.
+    fn result_for_mover(mover_is_player1: bool, score: Score) -> u32 {
+        match (mover_is_player1, score) {
+            (true, Score::Player1Wins) | (false, Score::Player2Wins) => 1,
+            (_, Score::Player2Wins) | (_, Score::Player1Wins) => 0,
+            (_, Score::Draw) => 1,
+            (_, Score::Undecided) => panic!("should not happen"),
+        }
+    }
 
-        // ??? Should modify in place, for efficiency, if the board is large
-        let next_state = state.perform(action);
+    /// Simulation phase: play random moves (the same policy as
+    /// `StrategyRandom`) from `state` down to a terminal position, without
+    /// creating any tree node, and report the resulting `Score`.
+    fn rollout(state: &GameState, rng: &mut dyn RngCore) -> Score {
+        let mut random = StrategyRandom::default();
+        let mut current = *state;
+        loop {
+            match current.score() {
+                Score::Undecided => {
+                    let action = random.play(&current, rng);
+                    current = current.perform(action);
+                }
+                score => return score,
+            }
+        }
+    }
 
-        // Create new node if needed
-        let child_node = {
-            let mut n = node.borrow_mut();
-            if n.moves[best.1].is_none() {
+    /// One full MCTS iteration: selection (follow UCB1 while all children
+    /// are already expanded), expansion (create exactly one new child),
+    /// simulation (random rollout from that child) and backpropagation
+    /// (update visited/wins on the path from root to the new child, each
+    /// relative to its own node's player to move).  Returns the `Score` the
+    /// playout ended with, so every caller up the recursion can derive its
+    /// own node's win increment from it.
+    fn search_one(&mut self, state: &GameState, rng: &mut dyn RngCore) -> Score {
+        let node = self.tree[state].clone();
+        let legal = state.legal_moves();
+
+        // Shuffle things, so that in case of equality we do not always use
+        // the first choice available.
+        let mut vec: Vec<usize> = (0..9).collect();
+        vec.shuffle(rng);
+
+        let unexplored: Vec<usize> = vec
+            .iter()
+            .copied()
+            .filter(|&idx| {
+                (legal.occupied & (1 << idx)) == 0
+                    && node.borrow().moves[idx].is_none()
+            })
+            .collect();
+
+        let (final_score, next_state, child_node) = if let Some(&idx) =
+            unexplored.first()
+        {
+            // Expansion: create exactly one new child for this unexplored move.
+            let action = Action::Put { mask: 1 << idx };
+            let next_state = state.perform(action);
+            let child_node = {
                 let a = match self.tree.get(&next_state) {
                     None => {
                         let a: Rc<RefCell<Node>> = Rc::default();
                         self.tree.insert(next_state, a.clone());
                         a
                     }
-                    Some(a) => {
-                        assert!(
-                            a.borrow().visited != 0,
-                            "created earlier, but never marked as visited {}",
-                            next_state,
-                        );
-                        a.clone()
-                    }
+                    Some(a) => a.clone(),
                 };
-                n.moves[best.1] = Some(a.clone());
+                node.borrow_mut().moves[idx] = Some(a.clone());
                 a
-            } else {
-                self.tree[&next_state].clone()
-            }
-        };
+            };
 
-        let result = match (current_player_is_1, next_state.score()) {
-            (true, Score::Player1Wins) | (false, Score::Player2Wins) => 1,
-            (_, Score::Player2Wins) | (_, Score::Player1Wins) => 0,
-            (_, Score::Draw) => 1,
-            (_, Score::Undecided) => {
-                self.search_one(&next_state, current_player_is_1, rng)
+            // Simulation: random rollout from the newly expanded child.
+            let final_score = match next_state.score() {
+                Score::Undecided => Self::rollout(&next_state, rng),
+                score => score,
+            };
+            (final_score, next_state, child_node)
+        } else {
+            // Selection: every legal move already has a child; we implement
+            // the UCBT algorithm, computing for each child:
+            //    ucb1 = (1 - wins/visited) + c * sqrt( ln(parent_visits) / visited)
+            // `wins`/`visited` on a child are relative to the child's own
+            // mover, i.e. our opponent, so `1 - wins/visited` is our own win
+            // rate through that child.  The second term encourages nodes
+            // that haven't been visited in a while.  We select the child
+            // with the highest ucb1.
+            //
+            // A node is a DAG vertex with several parents, so its own total
+            // visit count is what "parent visits" means here, rather than
+            // any single parent's visit count.  Selection only happens once
+            // every legal move has been expanded, and each expansion took
+            // place in an earlier visit of this node, so this count is never
+            // zero here and there is no spurious INFINITY case to
+            // special-case.
+            let parent_visits: u32 = node.borrow().visited;
+            let mut best = (f32::NEG_INFINITY, 0);
+
+            for idx in vec {
+                if (legal.occupied & (1 << idx)) != 0 {
+                    // position is already occupied
+                    continue;
+                }
+
+                let child_node = node.borrow().moves[idx].clone().unwrap();
+                let c = child_node.borrow();
+                let ucb1 = (1. - c.wins as f32 / c.visited as f32)
+                    + 1.4
+                        * ((parent_visits as f32).ln() / c.visited as f32)
+                            .sqrt();
+                drop(c);
+                if ucb1 > best.0 {
+                    best = (ucb1, idx);
+                }
             }
+
+            let action = Action::Put { mask: 1 << best.1 };
+            // ??? Should modify in place, for efficiency, if the board is large
+            let next_state = state.perform(action);
+            let child_node = node.borrow().moves[best.1].clone().unwrap();
+
+            let final_score = match next_state.score() {
+                Score::Undecided => self.search_one(&next_state, rng),
+                score => score,
+            };
+            (final_score, next_state, child_node)
         };
 
         let mut c = child_node.borrow_mut();
         c.visited += 1;
-        c.wins += result;
+        c.wins += Self::result_for_mover(next_state.is_player1, final_score);
 
-        result
+        final_score
     }
 }
 
@@ -161,11 +299,11 @@ impl Strategy for StrategyMCTS {
         "MonteCarlo".into()
     }
 
-    fn play(&mut self, state: &GameState, rng: &mut ThreadRng) -> Action {
-        //  The first  time, we basically do "offline" training and do a longer
-        //  exploration.  Afterwards, we just do a few iterations to further
-        //  improve the search.
-        let iterations = if self.tree.is_empty() { 30000 } else { 100 };
+    fn play(&mut self, state: &GameState, rng: &mut dyn RngCore) -> Action {
+        // `state` reflects every move actually played so far (ours and the
+        // opponent's); anything cached that `state` could not have grown
+        // into is dead weight, so reclaim it before searching further.
+        self.prune(state);
 
         let node = match self.tree.get(state) {
             None => {
@@ -176,25 +314,41 @@ impl Strategy for StrategyMCTS {
             Some(node) => node.clone(),
         };
 
-        // arbitrary limitations: 5477 is the total number of valide states in
-        // the game, so if we have already visited very often, stop searching.
-        if node.borrow().visited < 5477 {
-            for _ in 0..iterations {
-                let result = self.search_one(state, state.is_player1, rng);
-                let mut n = node.borrow_mut();
-                n.visited += 1;
-                n.wins += result;
+        // Anytime search: keep simulating until either the time budget is
+        // spent, or the node rooted here has been visited MAX_VISITS times,
+        // whichever comes first.  We only look at the clock every
+        // CLOCK_CHECK_INTERVAL simulations, since Instant::now() is not free
+        // and a single simulation is cheap.
+        self.last_simulations = 0;
+        let start = Instant::now();
+        loop {
+            if node.borrow().visited >= MAX_VISITS {
+                break;
+            }
+            let final_score = self.search_one(state, rng);
+            let mut n = node.borrow_mut();
+            n.visited += 1;
+            n.wins += Self::result_for_mover(state.is_player1, final_score);
+            drop(n);
+
+            self.last_simulations += 1;
+            if self.last_simulations % CLOCK_CHECK_INTERVAL == 0
+                && start.elapsed() >= self.max_time
+            {
+                break;
             }
         }
 
-        // Now select the child with the highest win rate
+        // Now select the child with the highest win rate for us: a child's
+        // own `wins`/`visited` are relative to its mover, i.e. our opponent,
+        // so our own win rate through that child is the complement.
         let mut best = (-1., 0);
         for idx in 0..9 {
             match &node.borrow().moves[idx] {
                 None => {}
                 Some(child_n) => {
                     let c = child_n.borrow();
-                    let rate = c.wins as f32 / c.visited as f32;
+                    let rate = 1. - c.wins as f32 / c.visited as f32;
                     if rate > best.0 {
                         best = (rate, idx);
                     }
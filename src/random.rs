@@ -1,7 +1,6 @@
 use crate::strategy::Strategy;
 use crate::types::{Action, GameState};
-use rand::rngs::ThreadRng;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 #[derive(Default)]
 pub struct StrategyRandom {}
@@ -11,7 +10,7 @@ impl Strategy for StrategyRandom {
         "Random".into()
     }
 
-    fn play(&mut self, state: &GameState, rng: &mut ThreadRng) -> Action {
+    fn play(&mut self, state: &GameState, rng: &mut dyn RngCore) -> Action {
         let legal = state.legal_moves();
         let mut choice = rng.gen_range(0..legal.occupied.count_zeros());
         let mut current = 1;
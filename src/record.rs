@@ -0,0 +1,199 @@
+use crate::types::{Action, GameState, Score};
+use std::collections::HashMap;
+
+/// A coarse positional evaluation attached to a node, in the spirit of SGF's
+/// `GB`/`GW`/`DM` evaluation marks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evaluation {
+    GoodForPlayer1,
+    Even,
+    GoodForPlayer2,
+}
+
+/// A move-quality annotation, in the spirit of SGF's `!`/`?` move marks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Annotation {
+    InterestingMove,
+    DoubtfulMove,
+    Blunder,
+}
+
+/// One move in a recorded game: the `Action` taken, the `GameState` it led
+/// to, and whatever commentary has been attached to it.  `variations` holds
+/// the moves that can follow from here; `variations[0]` (if any) is the
+/// mainline continuation, the rest are alternative lines explored off of it.
+pub struct Node {
+    pub action: Action,
+    pub state: GameState,
+    pub comment: Option<String>,
+    pub evaluation: Option<Evaluation>,
+    pub annotation: Option<Annotation>,
+    pub variations: Vec<Node>,
+}
+
+/// A recorded game: a tree of moves rooted at `start` (usually
+/// `GameState::default()`), rather than just the single current
+/// `GameState` the live strategies play against.  `variations[0]` (if any)
+/// is the first move of the mainline.
+#[derive(Default)]
+pub struct GameRecord {
+    pub start: GameState,
+    pub variations: Vec<Node>,
+}
+
+/// Walks the mainline (`variations[0]` at every level) from the start of a
+/// `GameRecord`, yielding each `Node` in the order the moves were played.
+pub struct Replay<'a> {
+    next: Option<&'a Node>,
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let current = self.next.take()?;
+        self.next = current.variations.first();
+        Some(current)
+    }
+}
+
+impl GameRecord {
+    pub fn replay(&self) -> Replay<'_> {
+        Replay { next: self.variations.first() }
+    }
+
+    /// Auto-annotate every move in every variation by comparing its
+    /// backed-up `Score` (from `table`, as produced by `solver::solve`)
+    /// against the best score available from the position before it was
+    /// played: a move that drops all the way to a loss is a `Blunder`, one
+    /// that merely throws away a win for a draw is a `DoubtfulMove`, and one
+    /// that holds the best available score right after the opponent handed
+    /// away theirs is flagged `InterestingMove`, the punishment found (or
+    /// kept finding).
+    pub fn auto_annotate(&mut self, table: &HashMap<GameState, Score>) {
+        for node in &mut self.variations {
+            annotate_subtree(self.start, false, node, table);
+        }
+    }
+}
+
+fn rank_for(is_player1: bool, score: Score) -> i8 {
+    match (is_player1, score) {
+        (true, Score::Player1Wins) | (false, Score::Player2Wins) => 1,
+        (_, Score::Draw) => 0,
+        (_, Score::Player1Wins) | (_, Score::Player2Wins) => -1,
+        (_, Score::Undecided) => panic!("should not happen"),
+    }
+}
+
+fn evaluation_of(score: Score) -> Evaluation {
+    match score {
+        Score::Player1Wins => Evaluation::GoodForPlayer1,
+        Score::Player2Wins => Evaluation::GoodForPlayer2,
+        Score::Draw => Evaluation::Even,
+        Score::Undecided => panic!("should not happen"),
+    }
+}
+
+/// `before` is mover's own state (whoever is about to play `node.action`);
+/// `opponent_blundered` is whether the move that led to `before` was itself
+/// annotated `Blunder`.
+fn annotate_subtree(before: GameState, opponent_blundered: bool, node: &mut Node, table: &HashMap<GameState, Score>) {
+    let best_rank = rank_for(before.is_player1, table[&before.canonical()]);
+    let actual = table[&node.state.canonical()];
+    let actual_rank = rank_for(before.is_player1, actual);
+
+    node.evaluation = Some(evaluation_of(actual));
+    node.annotation = if actual_rank < best_rank && actual_rank == -1 {
+        // Landed in a loss when a win or a draw was there for the taking,
+        // regardless of how far it fell from.
+        Some(Annotation::Blunder)
+    } else if actual_rank < best_rank {
+        // Still not lost, but threw away a win for a draw.
+        Some(Annotation::DoubtfulMove)
+    } else if opponent_blundered {
+        // The opponent just threw away their win or draw, and this move
+        // held onto the best score still available -- the refutation.
+        Some(Annotation::InterestingMove)
+    } else {
+        None
+    };
+
+    let blundered = node.annotation == Some(Annotation::Blunder);
+    let after = node.state;
+    for child in &mut node.variations {
+        annotate_subtree(after, blundered, child, table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn push_move(cursor: &mut Vec<Node>, state: GameState, mask: crate::types::Grid) -> GameState {
+        let state = state.perform(Action::Put { mask });
+        cursor.push(Node {
+            action: Action::Put { mask },
+            state,
+            comment: None,
+            evaluation: None,
+            annotation: None,
+            variations: Vec::new(),
+        });
+        state
+    }
+
+    #[test]
+    fn replay_walks_the_mainline_in_order() {
+        let mut record = GameRecord { start: GameState::default(), variations: Vec::new() };
+        let mut state = record.start;
+        let mut cursor = &mut record.variations;
+        for cell in [4, 0] {
+            state = push_move(cursor, state, 1 << cell);
+            cursor = &mut cursor.last_mut().unwrap().variations;
+        }
+
+        let masks: Vec<_> = record
+            .replay()
+            .map(|node| match node.action {
+                Action::Put { mask } => mask,
+            })
+            .collect();
+        assert_eq!(masks, vec![1 << 4, 1 << 0]);
+    }
+
+    #[test]
+    fn auto_annotate_flags_a_move_that_throws_away_a_draw_as_a_blunder() {
+        let table = crate::solver::solve();
+        let mut record = GameRecord { start: GameState::default(), variations: Vec::new() };
+        let mut state = record.start;
+        let mut cursor = &mut record.variations;
+        for cell in [4, 0, 8, 2, 6] {
+            state = push_move(cursor, state, 1 << cell);
+            cursor = &mut cursor.last_mut().unwrap().variations;
+        }
+
+        record.auto_annotate(&table);
+        let last = record.replay().last().unwrap();
+        assert_eq!(last.annotation, Some(Annotation::Blunder));
+    }
+
+    #[test]
+    fn perfect_play_is_never_annotated() {
+        let table = crate::solver::solve();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut record = GameRecord { start: GameState::default(), variations: Vec::new() };
+        let mut state = record.start;
+        let mut cursor = &mut record.variations;
+        while state.score() == Score::Undecided {
+            let (action, _) = crate::ai::best_move(&state, crate::ai::AIDifficulty::Hard, &mut rng);
+            let Action::Put { mask } = action;
+            state = push_move(cursor, state, mask);
+            cursor = &mut cursor.last_mut().unwrap().variations;
+        }
+
+        record.auto_annotate(&table);
+        assert!(record.replay().all(|node| node.annotation.is_none()));
+    }
+}
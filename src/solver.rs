@@ -0,0 +1,78 @@
+use crate::types::{Action, GameState, Grid, Score};
+use std::collections::HashMap;
+
+/// Prefer whichever of `a`/`b` is best for the player identified by
+/// `is_player1` (`Player1Wins` > `Draw` > `Player2Wins` from player1's
+/// perspective, and the reverse from player2's).
+fn better_for(is_player1: bool, a: Score, b: Score) -> Score {
+    fn rank(is_player1: bool, score: Score) -> i8 {
+        match (is_player1, score) {
+            (true, Score::Player1Wins) | (false, Score::Player2Wins) => 1,
+            (_, Score::Draw) => 0,
+            (_, Score::Player1Wins) | (_, Score::Player2Wins) => -1,
+            (_, Score::Undecided) => panic!("should not happen"),
+        }
+    }
+    if rank(is_player1, b) > rank(is_player1, a) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Fill `table` with the perfect-play `Score` of every canonical position
+/// reachable from `state`, recursing depth-first and memoizing on
+/// `GameState::canonical` so that the 5477 reachable states collapse to the
+/// 765 essentially-distinct ones.
+fn solve_from(state: &GameState, table: &mut HashMap<GameState, Score>) -> Score {
+    let canonical = state.canonical();
+    if let Some(&score) = table.get(&canonical) {
+        return score;
+    }
+
+    let score = match state.score() {
+        Score::Undecided => {
+            let legal = state.legal_moves();
+            let mut best = None;
+            for cell in 0..9 {
+                let mask: Grid = 1 << cell;
+                if legal.occupied & mask != 0 {
+                    continue;
+                }
+                let next = state.perform(Action::Put { mask });
+                let next_score = solve_from(&next, table);
+                best = Some(match best {
+                    None => next_score,
+                    Some(current) => better_for(state.is_player1, current, next_score),
+                });
+            }
+            best.unwrap()
+        }
+        score => score,
+    };
+
+    table.insert(canonical, score);
+    score
+}
+
+/// Solve the whole game from the opening position, returning a
+/// transposition table keyed by `GameState::canonical` with the
+/// perfect-play `Score` of every one of the 765 essentially-distinct
+/// positions.
+pub fn solve() -> HashMap<GameState, Score> {
+    let mut table = HashMap::new();
+    solve_from(&GameState::default(), &mut table);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opening_position_is_a_forced_draw() {
+        let table = solve();
+        assert_eq!(table.len(), 765);
+        assert_eq!(table[&GameState::default().canonical()], Score::Draw);
+    }
+}
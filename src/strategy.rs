@@ -1,7 +1,7 @@
 use crate::types::{Action, GameState};
-use rand::rngs::ThreadRng;
+use rand::RngCore;
 
 pub trait Strategy {
     fn name(&self) -> String;
-    fn play(&mut self, state: &GameState, rng: &mut ThreadRng) -> Action;
+    fn play(&mut self, state: &GameState, rng: &mut dyn RngCore) -> Action;
 }
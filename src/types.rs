@@ -1,11 +1,42 @@
 pub type Grid = u16;
 
+/// Bits 9..16 of `player1`/`player2` are a sentinel (see `GameState::default`)
+/// that must never be touched by a symmetry, only bits 0..9 describe the
+/// board itself.
+pub(crate) const SENTINEL: Grid = !0b111111111;
+
+/// The 8 symmetries of the dihedral group of the square (4 rotations times 2
+/// reflections), each given as, for every destination cell (0-8, row major),
+/// the source cell it is taken from.
+const SYMMETRIES: [[usize; 9]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8], // identity
+    [6, 3, 0, 7, 4, 1, 8, 5, 2], // rotate 90
+    [8, 7, 6, 5, 4, 3, 2, 1, 0], // rotate 180
+    [2, 5, 8, 1, 4, 7, 0, 3, 6], // rotate 270
+    [2, 1, 0, 5, 4, 3, 8, 7, 6], // flip horizontal
+    [6, 7, 8, 3, 4, 5, 0, 1, 2], // flip vertical
+    [0, 3, 6, 1, 4, 7, 2, 5, 8], // flip diagonal
+    [8, 5, 2, 7, 4, 1, 6, 3, 0], // flip anti-diagonal
+];
+
+fn permute(mask: Grid, perm: &[usize; 9]) -> Grid {
+    let mut out: Grid = 0;
+    for (dst, &src) in perm.iter().enumerate() {
+        if mask & (1 << src) != 0 {
+            out |= 1 << dst;
+        }
+    }
+    out | SENTINEL
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     Put { mask: Grid },
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Score {
     Player1Wins,
     Player2Wins,
@@ -17,6 +48,18 @@ pub struct LegalMoves {
     pub occupied: Grid, //  bit is 1 if the cell is occupied
 }
 
+impl LegalMoves {
+    /// Check that `mask` names exactly one on-board cell that isn't already
+    /// occupied, the one rule every move-legality check in the crate
+    /// (`agent::play_match`, `lobby::Game::make_move`) shares.
+    pub fn validate(&self, mask: Grid) -> Result<(), crate::errors::Error> {
+        if mask == 0 || mask.count_ones() != 1 || mask & SENTINEL != 0 || self.occupied & mask != 0 {
+            return Err(crate::errors::Error::IllegalMove(mask));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct GameState {
     pub player1: Grid,    // bit set to 1 if player1 occupies the cell
@@ -61,6 +104,41 @@ impl GameState {
         }
     }
 
+    /// Collapse `self` to the canonical representative of its symmetry
+    /// class: the symmetry (among the 8 in `SYMMETRIES`) that yields the
+    /// lexicographically smallest `(player1, player2)` pair.  Using this as
+    /// a transposition-table key collapses the 5477 reachable states down
+    /// to the 765 essentially-distinct positions.
+    pub fn canonical(&self) -> GameState {
+        self.canonical_with_symmetry().0
+    }
+
+    /// Like `canonical`, but also returns the index (into `SYMMETRIES`) of
+    /// the symmetry used, so a move computed on the canonical form can be
+    /// mapped back to `self`'s own coordinates with `map_move_from_symmetry`.
+    pub(crate) fn canonical_with_symmetry(&self) -> (GameState, usize) {
+        let mut best = (*self, 0);
+        for (sym, perm) in SYMMETRIES.iter().enumerate().skip(1) {
+            let candidate = GameState {
+                player1: permute(self.player1, perm),
+                player2: permute(self.player2, perm),
+                is_player1: self.is_player1,
+            };
+            if (candidate.player1, candidate.player2) < (best.0.player1, best.0.player2) {
+                best = (candidate, sym);
+            }
+        }
+        best
+    }
+
+    /// Map a single-bit move mask expressed in the coordinates of the
+    /// canonical form produced by symmetry `sym` back to this state's own
+    /// coordinates.
+    pub(crate) fn map_move_from_symmetry(canonical_mask: Grid, sym: usize) -> Grid {
+        let cell = canonical_mask.trailing_zeros() as usize;
+        1 << SYMMETRIES[sym][cell]
+    }
+
     pub fn score(&self) -> Score {
         if self.player1 & 0b000000111 == 0b000000111
             || self.player1 & 0b000111000 == 0b000111000
@@ -110,3 +188,39 @@ impl std::fmt::Display for GameState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_is_invariant_under_every_symmetry() {
+        let state = GameState::default()
+            .perform(Action::Put { mask: 1 << 0 })
+            .perform(Action::Put { mask: 1 << 4 });
+        let canonical = state.canonical();
+
+        for perm in &SYMMETRIES {
+            let rotated = GameState {
+                player1: permute(state.player1, perm),
+                player2: permute(state.player2, perm),
+                is_player1: state.is_player1,
+            };
+            let rotated_canonical = rotated.canonical();
+            assert_eq!(rotated_canonical.player1, canonical.player1);
+            assert_eq!(rotated_canonical.player2, canonical.player2);
+        }
+    }
+
+    #[test]
+    fn map_move_from_symmetry_round_trips_to_an_on_board_cell() {
+        let state = GameState::default().perform(Action::Put { mask: 1 << 1 });
+        let (_, sym) = state.canonical_with_symmetry();
+
+        for cell in 0..9 {
+            let mapped = GameState::map_move_from_symmetry(1 << cell, sym);
+            assert_eq!(mapped.count_ones(), 1);
+            assert_eq!(mapped & SENTINEL, 0);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Wire format for `GameState`: the packed bitboards with their sentinel
+//! high bits are an implementation detail, so persisting or sending a
+//! `GameState` (e.g. over `serde_cbor`, as the Solana tic-tac-toe program
+//! does) instead serializes the 9 cells as `X`/`O`/`.` plus the side to
+//! move, and reconstructs the sentinel-masked `Grid` fields on load.
+#![cfg(feature = "serde")]
+
+use crate::types::{GameState, Grid, SENTINEL};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Wire {
+    cells: [char; 9],
+    is_player1: bool,
+}
+
+impl From<&GameState> for Wire {
+    fn from(state: &GameState) -> Self {
+        let mut cells = ['.'; 9];
+        for (i, cell) in cells.iter_mut().enumerate() {
+            let bit = 1 << i;
+            *cell = if state.player1 & bit != 0 {
+                'X'
+            } else if state.player2 & bit != 0 {
+                'O'
+            } else {
+                '.'
+            };
+        }
+        Wire { cells, is_player1: state.is_player1 }
+    }
+}
+
+impl TryFrom<Wire> for GameState {
+    type Error = String;
+
+    fn try_from(wire: Wire) -> Result<Self, Self::Error> {
+        let mut player1: Grid = SENTINEL;
+        let mut player2: Grid = SENTINEL;
+        for (i, &c) in wire.cells.iter().enumerate() {
+            let bit = 1 << i;
+            match c {
+                'X' => player1 |= bit,
+                'O' => player2 |= bit,
+                '.' => {}
+                other => return Err(format!("invalid cell marker {:?}", other)),
+            }
+        }
+        // Can't actually happen with this cell-per-array-slot representation
+        // (each slot holds at most one marker), but a from-the-wire payload
+        // is untrusted input, so check the invariant rather than assume it.
+        if player1 & player2 & !SENTINEL != 0 {
+            return Err("a cell is claimed by both players".into());
+        }
+        Ok(GameState { player1, player2, is_player1: wire.is_player1 })
+    }
+}
+
+impl serde::Serialize for GameState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Wire::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for GameState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let wire = Wire::deserialize(deserializer)?;
+        GameState::try_from(wire).map_err(serde::de::Error::custom)
+    }
+}
+
+impl GameState {
+    /// Serialize to the compact CBOR wire format (see module docs).
+    pub fn to_cbor(self) -> Vec<u8> {
+        serde_cbor::to_vec(&self).expect("serializing a GameState cannot fail")
+    }
+
+    /// Reconstruct a `GameState` from bytes produced by `to_cbor`.
+    pub fn from_cbor(bytes: &[u8]) -> Result<GameState, serde_cbor::Error> {
+        serde_cbor::from_slice(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Action;
+
+    #[test]
+    fn cbor_round_trips_a_game_in_progress() {
+        let state = GameState::default()
+            .perform(Action::Put { mask: 1 << 4 })
+            .perform(Action::Put { mask: 1 << 0 });
+        let bytes = state.to_cbor();
+        let round_tripped = GameState::from_cbor(&bytes).unwrap();
+        assert_eq!(round_tripped.player1, state.player1);
+        assert_eq!(round_tripped.player2, state.player2);
+        assert_eq!(round_tripped.is_player1, state.is_player1);
+    }
+
+    #[test]
+    fn an_invalid_cell_marker_is_rejected() {
+        let mut cells = ['.'; 9];
+        cells[0] = 'Z';
+        let result = GameState::try_from(Wire { cells, is_player1: true });
+        assert!(matches!(result, Err(ref e) if e.contains("invalid cell marker")));
+    }
+}